@@ -11,16 +11,26 @@
 
 use std::time::{Duration, Instant};
 
+pub mod pool;
+
 pub fn phi_from_prob(x: f64) -> f64 {
     assert!(0. <= x  && x <= 1.);
     -f64::log10(x)
 }
+/// The accumulation strategy used by a `PingWindow`.
+enum WindowMode {
+    /// Every interval seen so far contributes equally.
+    /// `mean` and `m2` are Welford's online moments, numerically stable
+    /// against the catastrophic cancellation that a naive `sum`/`sum2`
+    /// accumulation suffers from over long lifetimes.
+    Window { n: usize, mean: f64, m2: f64 },
+    /// Recent intervals are weighted more heavily via forward decay.
+    Ewma { alpha: f64, mu: f64, var: f64 },
+}
 /// Set of recent N ping intervals.
 pub struct PingWindow {
-    n: usize,
     last_ping: Instant,
-    sum: f64,
-    sum2: f64,
+    mode: WindowMode,
 }
 impl PingWindow {
     pub fn new() -> Self {
@@ -30,10 +40,35 @@ impl PingWindow {
         // as actual heartbeats (usually much shorter than the initial value) fill the window.
         let deadline = Duration::from_secs(5);
         Self {
-            n: 1,
             last_ping: now,
-            sum: deadline.as_millis() as f64,
-            sum2: 0.,
+            mode: WindowMode::Window {
+                n: 1,
+                mean: deadline.as_millis() as f64,
+                m2: 0.,
+            },
+        }
+    }
+    /// Like `new` but weights recent intervals more heavily than old ones,
+    /// using an exponentially-weighted moving mean and variance instead of
+    /// a uniformly-weighted window.
+    ///
+    /// `alpha` is the decay factor in `(0, 1)`: the closer to 1, the faster
+    /// the detector forgets old intervals and reacts to a new baseline.
+    /// Unlike `new`, this keeps only `mu` and `var` so there's no `n`
+    /// bookkeeping.
+    pub fn new_ewma(alpha: f64) -> Self {
+        assert!(0. < alpha && alpha < 1.);
+        let now = Instant::now();
+        // same initial seed as `new`: a super long value that gets
+        // diluted by actual heartbeats as they come in.
+        let deadline = Duration::from_secs(5);
+        Self {
+            last_ping: now,
+            mode: WindowMode::Ewma {
+                alpha,
+                mu: deadline.as_millis() as f64,
+                var: 0.,
+            },
         }
     }
     pub fn last_ping(&self) -> Instant {
@@ -41,29 +76,37 @@ impl PingWindow {
     }
     pub fn add_ping(&mut self, ping: Instant) {
         assert!(ping > self.last_ping);
-        // window size too large is found meaningless in experiment.
-        // not only that, may harm by counting in old values. (e.g. latency change, overflow)
-        // the experiment shows the error rate saturate around n=10000.
-        if self.n == 10000 {
-            self.sum = self.sum / self.n as f64 * (self.n-1) as f64;
-            // suppose each value has equal contribution to the variance.
-            self.sum2 = self.sum2 / self.n as f64 * (self.n-1) as f64;
-            self.n -= 1;
-        }
         let v = (ping - self.last_ping).as_millis() as f64;
         self.last_ping = ping;
-        self.sum += v;
-        self.n += 1;
-        let mu = self.sum / self.n as f64;
-        self.sum2 += (v - mu) * (v - mu);
+        match &mut self.mode {
+            WindowMode::Window { n, mean, m2 } => {
+                // Welford's update: `mean` stays on the scale of a single
+                // interval and `m2` sums deviations from the running mean
+                // rather than raw squares, so precision holds over a long
+                // lifetime and there's no cap to rescale on the per-ping path.
+                *n += 1;
+                let delta = v - *mean;
+                *mean += delta / *n as f64;
+                *m2 += delta * (v - *mean);
+            }
+            WindowMode::Ewma { alpha, mu, var } => {
+                let delta = v - *mu;
+                *mu += *alpha * delta;
+                *var = (1. - *alpha) * (*var + *alpha * delta * delta);
+            }
+        }
     }
     /// Make the current normal distribution based on the ping history.
     pub fn normal_dist(&self) -> NormalDist {
-        let n = self.n;
-        let mu = self.sum / n as f64;
-        let sigma = f64::sqrt(self.sum2 / n as f64);
-        NormalDist {
-            mu, sigma,
+        match &self.mode {
+            WindowMode::Window { n, mean, m2 } => {
+                let sigma = f64::sqrt(m2 / *n as f64);
+                NormalDist { mu: *mean, sigma }
+            }
+            WindowMode::Ewma { mu, var, .. } => {
+                let sigma = f64::sqrt(*var);
+                NormalDist { mu: *mu, sigma }
+            }
         }
     }
 }
@@ -83,21 +126,21 @@ impl NormalDist {
     }
     /// Calculate integral [x, inf]
     /// This is a monotonically decreasing function.
+    ///
+    /// Branchless: both tails of the logistic CDF approximation reduce to
+    /// the same `e / (1 + e)` expression, they only differ in which side of
+    /// `mu` the exponent's sign comes from. We compute the (always safe to
+    /// exponentiate) magnitude once and pick the side with `copysign`
+    /// instead of a data-dependent `if`, which is unpredictable when
+    /// sweeping phi across many heterogeneous servers.
     fn integral(&self, x: f64) -> f64 {
         // any small sigma rounds up to 1ms
         // which is negligible in the latency context.
-        let sigma = if self.sigma < 1. {
-            1.
-        } else {
-            self.sigma
-        };
+        let sigma = f64::max(self.sigma, 1.);
         let y = (x - self.mu) / sigma;
-        let e = f64::exp(-y * (1.5976 + 0.070566 * y * y));
-        if x > self.mu {
-            e / (1. + e)
-        } else {
-            1. - 1./(1. + e)
-        }
+        let ay = f64::abs(y);
+        let e = f64::exp(-ay * (1.5976 + 0.070566 * ay * ay));
+        0.5 + 0.5 * f64::copysign((1. - e) / (1. + e), -y)
     }
     /// Calculate the phi from the current normal distribution
     /// and the duration from the last ping.
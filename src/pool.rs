@@ -0,0 +1,124 @@
+//! A collection of `PingWindow`s, one per remote server, suited to
+//! monitoring thousands of peers from concurrently-running workers.
+//!
+//! Each slot is padded to the destructive-interference size (64 bytes on
+//! most platforms) so that two independently-updated detectors never share
+//! a cache line. Without this, concurrent `add_ping` calls on neighboring
+//! servers would cause false sharing and tank throughput. The padding also
+//! means a background sweep computing phi for every server (`sweep_phi`)
+//! walks the slots contiguously, one cache line per detector, rather than
+//! chasing pointers through a map.
+
+use crate::PingWindow;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Identifies a detector within a `DetectorPool`.
+pub type DetectorId = usize;
+
+/// A single slot, padded/aligned to a cache line so it never shares one
+/// with its neighbors.
+#[repr(align(64))]
+struct Slot(Mutex<PingWindow>);
+
+/// Per-server failure detectors, indexed by `DetectorId`.
+pub struct DetectorPool {
+    slots: Vec<Slot>,
+}
+impl DetectorPool {
+    pub fn new() -> Self {
+        Self { slots: vec![] }
+    }
+    /// Register a new server and start its detector.
+    pub fn insert(&mut self) -> DetectorId {
+        self.slots.push(Slot(Mutex::new(PingWindow::new())));
+        self.slots.len() - 1
+    }
+    /// Record a ping from the server identified by `id`.
+    pub fn add_ping(&self, id: DetectorId, now: Instant) {
+        self.slots[id].0.lock().unwrap().add_ping(now);
+    }
+    /// Calculate phi for the server identified by `id` at time `now`.
+    pub fn phi(&self, id: DetectorId, now: Instant) -> f64 {
+        let window = self.slots[id].0.lock().unwrap();
+        let elapsed = now.saturating_duration_since(window.last_ping());
+        window.normal_dist().phi(elapsed)
+    }
+    /// Calculate phi for every registered detector at time `now`, in
+    /// insertion order. Suited to a background sweep over thousands of
+    /// servers since it streams linearly through the padded slots.
+    pub fn sweep_phi(&self, now: Instant) -> Vec<f64> {
+        self.slots
+            .iter()
+            .map(|slot| {
+                let window = slot.0.lock().unwrap();
+                let elapsed = now.saturating_duration_since(window.last_ping());
+                window.normal_dist().phi(elapsed)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_insert_add_ping_phi() {
+        let mut pool = DetectorPool::new();
+        let id = pool.insert();
+        let base = Instant::now();
+        pool.add_ping(id, base + Duration::from_millis(1));
+        let phi = pool.phi(id, base + Duration::from_millis(2));
+        dbg!(phi);
+    }
+
+    #[test]
+    fn test_phi_does_not_panic_on_stale_now() {
+        let mut pool = DetectorPool::new();
+        let id = pool.insert();
+        let base = Instant::now();
+        pool.add_ping(id, base + Duration::from_millis(10));
+        // `now` predates the detector's last ping: must saturate, not underflow-panic.
+        let phi = pool.phi(id, base);
+        dbg!(phi);
+    }
+
+    #[test]
+    fn test_sweep_phi_covers_every_detector() {
+        let mut pool = DetectorPool::new();
+        let ids: Vec<_> = (0..4).map(|_| pool.insert()).collect();
+        let base = Instant::now();
+        for &id in &ids {
+            pool.add_ping(id, base + Duration::from_millis(1));
+        }
+        let phis = pool.sweep_phi(base + Duration::from_millis(2));
+        assert_eq!(phis.len(), ids.len());
+    }
+
+    #[test]
+    fn test_concurrent_add_ping_across_detectors() {
+        let mut pool = DetectorPool::new();
+        let ids: Vec<_> = (0..8).map(|_| pool.insert()).collect();
+        let pool = Arc::new(pool);
+        let base = Instant::now();
+        let handles: Vec<_> = ids
+            .into_iter()
+            .map(|id| {
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    for i in 1..=100u64 {
+                        pool.add_ping(id, base + Duration::from_millis(i));
+                    }
+                    pool.phi(id, base + Duration::from_millis(101))
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
+}